@@ -236,6 +236,12 @@ pub type ScriptOptions = types::ScriptOptions;
 /// Io Options available for invoking the script
 pub type IoOptions = types::IoOptions;
 
+/// The shell used to invoke the script
+pub type Shell = types::Shell;
+
+/// A callback invoked once per line of streamed output
+pub type LineCallback = types::LineCallback;
+
 /// Invokes the provided script content and returns the invocation output.
 ///
 /// # Arguments
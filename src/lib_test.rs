@@ -0,0 +1,126 @@
+//! # lib_test
+//!
+//! Tests exercising the API in `lib.rs` and the types it exposes.
+//!
+
+use crate::types::{ErrorInfo, Runtime, Shell, Stage};
+use crate::{run, IoOptions, ScriptError, ScriptOptions};
+use std::error::Error;
+use std::fs::create_dir_all;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn env_and_working_directory_are_visible_inside_the_script() {
+    let mut dir = std::env::temp_dir();
+    dir.push("run script test dir");
+    create_dir_all(&dir).unwrap();
+
+    let mut options = ScriptOptions::new();
+    options.working_directory = Some(dir.clone());
+    options
+        .env
+        .insert("RUN_SCRIPT_TEST_VAR".to_string(), "hello".to_string());
+
+    let script = r#"
+echo "$RUN_SCRIPT_TEST_VAR"
+pwd
+"#;
+
+    let (code, output, _) = run(script, &vec![], &options).unwrap();
+
+    let mut lines = output.lines();
+    assert_eq!(code, 0);
+    assert_eq!(lines.next(), Some("hello"));
+    assert_eq!(lines.next(), Some(dir.to_str().unwrap()));
+}
+
+#[test]
+fn script_error_exit_code_reports_stage_display_and_no_source() {
+    let error = ScriptError {
+        stage: Stage::NonZeroExit,
+        info: ErrorInfo::ExitCode {
+            code: 2,
+            stdout: "out".to_string(),
+            stderr: "err".to_string(),
+        },
+    };
+
+    assert_eq!(
+        error.to_string(),
+        "non-zero exit: script exited with code 2"
+    );
+    assert!(error.source().is_none());
+}
+
+#[test]
+fn embedded_runtime_maps_exit_code_and_rejects_unsupported_capture_options() {
+    let mut options = ScriptOptions::new();
+    options.runtime = Runtime::Embedded;
+    options.capture_output = IoOptions::Inherit;
+
+    let (code, _, _) = run("exit 7", &vec![], &options).unwrap();
+    assert_eq!(code, 7);
+
+    options.capture_output = IoOptions::Pipe;
+    assert!(run("exit 0", &vec![], &options).is_err());
+
+    options.capture_output = IoOptions::Inherit;
+    options.capture_input = IoOptions::Null;
+    assert!(run("exit 0", &vec![], &options).is_err());
+}
+
+#[test]
+fn powershell_shell_follows_its_own_invocation_conventions() {
+    let shell = Shell::PowerShell;
+
+    assert_eq!(shell.extension(), "ps1");
+    assert_eq!(shell.executable(), "powershell");
+    assert_eq!(
+        shell.launch_args("script.ps1"),
+        vec![
+            "-NoProfile".to_string(),
+            "-File".to_string(),
+            "script.ps1".to_string(),
+        ]
+    );
+    assert!(!shell.supports_shebang());
+    assert_eq!(
+        shell.change_directory_command("C:\\Program Files\\app"),
+        "Set-Location \"C:\\Program Files\\app\""
+    );
+
+    let mut options = ScriptOptions::new();
+    options.exit_on_error = true;
+    options.print_commands = true;
+
+    assert_eq!(
+        shell.prologue_lines(&options),
+        vec![
+            "$ErrorActionPreference = 'Stop'".to_string(),
+            "Set-PSDebug -Trace 1".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn on_stdout_line_is_invoked_for_each_line_as_it_streams() {
+    let lines = Arc::new(Mutex::new(vec![]));
+    let callback_lines = lines.clone();
+
+    let mut options = ScriptOptions::new();
+    options.on_stdout_line = Some(Arc::new(Mutex::new(move |line: &str| {
+        callback_lines.lock().unwrap().push(line.to_string());
+    })));
+
+    let script = r#"
+echo one
+echo two
+echo three
+"#;
+
+    let (code, output, _) = run(script, &vec![], &options).unwrap();
+
+    assert_eq!(code, 0);
+    assert_eq!(output.trim(), "one\ntwo\nthree");
+    assert_eq!(*lines.lock().unwrap(), vec!["one", "two", "three"]);
+}
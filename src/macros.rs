@@ -0,0 +1,38 @@
+//! # macros
+//!
+//! Defines helper macros for invoking scripts without explicitly building the arguments/options.
+//!
+
+/// Invokes the provided script content and returns the invocation output.
+///
+/// This is the macro version of the [run](fn.run.html) function, enabling shorter invocations
+/// by providing default values for the arguments and/or options.
+#[macro_export]
+macro_rules! run_script {
+    ($script:expr) => {
+        $crate::run($script, &vec![], &$crate::ScriptOptions::new())
+    };
+    ($script:expr, $options:expr) => {
+        $crate::run($script, &vec![], $options)
+    };
+    ($script:expr, $args:expr, $options:expr) => {
+        $crate::run($script, $args, $options)
+    };
+}
+
+/// Invokes the provided script content and returns a process handle.
+///
+/// This is the macro version of the [spawn](fn.spawn.html) function, enabling shorter invocations
+/// by providing default values for the arguments and/or options.
+#[macro_export]
+macro_rules! spawn_script {
+    ($script:expr) => {
+        $crate::spawn($script, &vec![], &$crate::ScriptOptions::new())
+    };
+    ($script:expr, $options:expr) => {
+        $crate::spawn($script, &vec![], $options)
+    };
+    ($script:expr, $args:expr, $options:expr) => {
+        $crate::spawn($script, $args, $options)
+    };
+}
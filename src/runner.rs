@@ -7,16 +7,33 @@
 #[path = "./runner_test.rs"]
 mod runner_test;
 
-use crate::types::{ErrorInfo, ScriptError, ScriptOptions, IoOptions};
+use crate::types::{
+    ErrorInfo, IoOptions, LineCallback, Runtime, ScriptError, ScriptOptions, Shell, Stage,
+};
+use duckscript::runner as duck_runner;
+use duckscript::types::runtime::Context as DuckscriptContext;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use std::collections::HashMap;
 use std::env;
 use std::env::current_dir;
 use std::fs::{create_dir_all, remove_file, File};
 use std::io::prelude::*;
-use std::io::Error;
+use std::io::{BufReader, Error};
 use std::iter;
 use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(not(windows))]
+use std::os::unix::process::CommandExt;
+
+#[cfg(not(windows))]
+use nix::sys::signal::{kill, Signal};
+#[cfg(not(windows))]
+use nix::unistd::Pid;
 
 #[cfg(not(windows))]
 use users::get_current_username;
@@ -45,6 +62,18 @@ fn create_command_builder(
         command.arg(arg);
     }
 
+    if options.clear_env {
+        command.env_clear();
+    }
+
+    command.envs(&options.env);
+
+    // Run the child in its own process group so that, on timeout, `kill_child_tree` can kill
+    // the whole group rather than just the immediate shell; otherwise any grandchild process
+    // the script spawns is left running (and can keep the stdout/stderr pipes open forever).
+    #[cfg(not(windows))]
+    command.process_group(0);
+
     match options.capture_input {
         IoOptions::Null => command.stdin(Stdio::null()),
         IoOptions::Inherit => command.stdin(Stdio::inherit()),
@@ -82,7 +111,7 @@ fn get_additional_temp_path() -> Option<String> {
     }
 }
 
-fn create_script_file(script: &String) -> Result<String, Error> {
+fn create_script_file(script: &String, extension: &str) -> Result<String, Error> {
     let name = env!("CARGO_PKG_NAME");
 
     let mut rng = thread_rng();
@@ -104,11 +133,7 @@ fn create_script_file(script: &String) -> Result<String, Error> {
     match create_dir_all(&file_path) {
         Ok(_) => {
             file_path.push(file_name);
-            if cfg!(windows) {
-                file_path.set_extension("bat");
-            } else {
-                file_path.set_extension("sh");
-            };
+            file_path.set_extension(extension);
 
             let file_path_str = &file_path.to_str().unwrap_or("");
 
@@ -128,14 +153,22 @@ fn create_script_file(script: &String) -> Result<String, Error> {
     }
 }
 
-fn modify_script(script: &String, options: &ScriptOptions) -> Result<String, ScriptError> {
-    match current_dir() {
+fn modify_script(
+    script: &String,
+    options: &ScriptOptions,
+    shell: &Shell,
+) -> Result<String, ScriptError> {
+    let cwd_result = match options.working_directory {
+        Some(ref dir) => Ok(dir.clone()),
+        None => current_dir(),
+    };
+
+    match cwd_result {
         Ok(cwd_holder) => {
             match cwd_holder.to_str() {
                 Some(cwd) => {
                     // create cd command
-                    let mut cd_command = "cd ".to_string();
-                    cd_command.push_str(cwd);
+                    let cd_command = shell.change_directory_command(cwd);
 
                     let mut script_lines: Vec<String> = script
                         .trim()
@@ -144,23 +177,18 @@ fn modify_script(script: &String, options: &ScriptOptions) -> Result<String, Scr
                         .collect();
 
                     // check if first line is shebang line
-                    let mut insert_index =
-                        if script_lines.len() > 0 && script_lines[0].starts_with("#!") {
-                            1
-                        } else {
-                            0
-                        };
-
-                    if !cfg!(windows) {
-                        if options.exit_on_error {
-                            script_lines.insert(insert_index, "set -e".to_string());
-                            insert_index = insert_index + 1;
-                        }
-
-                        if options.print_commands {
-                            script_lines.insert(insert_index, "set -x".to_string());
-                            insert_index = insert_index + 1;
-                        }
+                    let mut insert_index = if shell.supports_shebang()
+                        && script_lines.len() > 0
+                        && script_lines[0].starts_with("#!")
+                    {
+                        1
+                    } else {
+                        0
+                    };
+
+                    for prologue_line in shell.prologue_lines(&options) {
+                        script_lines.insert(insert_index, prologue_line);
+                        insert_index = insert_index + 1;
                     }
 
                     script_lines.insert(insert_index, cd_command);
@@ -172,6 +200,7 @@ fn modify_script(script: &String, options: &ScriptOptions) -> Result<String, Scr
                     Ok(updated_script)
                 }
                 None => Err(ScriptError {
+                    stage: Stage::ScriptFileCreation,
                     info: ErrorInfo::Description(
                         "Unable to extract current working directory path.",
                     ),
@@ -179,35 +208,184 @@ fn modify_script(script: &String, options: &ScriptOptions) -> Result<String, Scr
             }
         }
         Err(error) => Err(ScriptError {
+            stage: Stage::ScriptFileCreation,
             info: ErrorInfo::IOError(error),
         }),
     }
 }
 
+/// Guards the process-global environment and current working directory while they are being
+/// relied upon: mutated for the duration of a `Runtime::Embedded` run, or merely read (via the
+/// ambient `current_dir()`) while preparing a `Runtime::ExternalShell` run with no explicit
+/// `working_directory`. Held for the life of both operations so that one can never observe the
+/// other's state mid-flight, since both act on process-global (not per-call) state.
+static ENV_CWD_LOCK: Mutex<()> = Mutex::new(());
+
+/// Captures the current process environment so it can be restored later by `restore_env`.
+fn snapshot_env() -> HashMap<String, String> {
+    env::vars().collect()
+}
+
+/// Restores the process environment to a previous `snapshot_env` result.
+fn restore_env(snapshot: HashMap<String, String>) {
+    for (key, _) in env::vars() {
+        if !snapshot.contains_key(&key) {
+            env::remove_var(key);
+        }
+    }
+
+    for (key, value) in snapshot {
+        env::set_var(key, value);
+    }
+}
+
+/// Loads and runs the script with the embedded duckscript interpreter.
+fn run_embedded_script(
+    script: &str,
+    args: &Vec<String>,
+    options: &ScriptOptions,
+) -> Result<(i32, String, String), ScriptError> {
+    let mut context = DuckscriptContext::new();
+
+    match duckscriptsdk::load(&mut context.commands) {
+        Ok(_) => (),
+        Err(_) => {
+            return Err(ScriptError {
+                stage: Stage::Spawn,
+                info: ErrorInfo::Description("Unable to load the duckscript standard library."),
+            })
+        }
+    };
+
+    for (index, arg) in args.iter().enumerate() {
+        context
+            .variables
+            .insert((index + 1).to_string(), arg.clone());
+    }
+
+    match duck_runner::run_script(&script.to_string(), context) {
+        Ok((_, exit_code)) => {
+            let code = exit_code.unwrap_or(0);
+
+            if options.exit_on_error && code != 0 {
+                Err(ScriptError {
+                    stage: Stage::NonZeroExit,
+                    info: ErrorInfo::ExitCode {
+                        code,
+                        stdout: String::new(),
+                        stderr: String::new(),
+                    },
+                })
+            } else {
+                Ok((code, String::new(), String::new()))
+            }
+        }
+        Err(_) => Err(ScriptError {
+            stage: Stage::Wait,
+            info: ErrorInfo::Description("Embedded duckscript execution failed."),
+        }),
+    }
+}
+
+/// Invokes the script using the embedded duckscript interpreter, bypassing the temp-file and
+/// external shell process used by `Runtime::ExternalShell`.
+///
+/// The interpreter runs in-process, so `env`/`clear_env`/`working_directory` are applied
+/// directly to the calling process for the duration of the run (saved beforehand and restored
+/// afterwards) rather than to a child process. `capture_input`/`capture_output` have no
+/// in-process equivalent of a child's stdio pipes; redirecting the process's own stdout/stderr
+/// would require unsafe file descriptor manipulation, which this crate does not use, so any
+/// value other than `IoOptions::Inherit` for either option is rejected up front instead of
+/// being silently ignored.
+fn run_embedded(
+    script: &str,
+    args: &Vec<String>,
+    options: &ScriptOptions,
+) -> Result<(i32, String, String), ScriptError> {
+    if options.capture_input != IoOptions::Inherit {
+        return Err(ScriptError {
+            stage: Stage::Spawn,
+            info: ErrorInfo::Description(
+                "Runtime::Embedded has no child process stdin to redirect; capture_input must \
+                 be IoOptions::Inherit.",
+            ),
+        });
+    }
+
+    if options.capture_output != IoOptions::Inherit {
+        return Err(ScriptError {
+            stage: Stage::Spawn,
+            info: ErrorInfo::Description(
+                "Runtime::Embedded runs in-process and cannot redirect its own stdout/stderr; \
+                 capture_output must be IoOptions::Inherit.",
+            ),
+        });
+    }
+
+    let _guard = ENV_CWD_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let env_snapshot = snapshot_env();
+    let cwd_snapshot = current_dir().ok();
+
+    if options.clear_env {
+        for (key, _) in env::vars() {
+            env::remove_var(key);
+        }
+    }
+
+    for (key, value) in options.env.iter() {
+        env::set_var(key, value);
+    }
+
+    let cwd_result = match options.working_directory {
+        Some(ref dir) => env::set_current_dir(dir),
+        None => Ok(()),
+    };
+
+    let result = match cwd_result {
+        Ok(_) => run_embedded_script(script, args, options),
+        Err(error) => Err(ScriptError {
+            stage: Stage::Spawn,
+            info: ErrorInfo::IOError(error),
+        }),
+    };
+
+    restore_env(env_snapshot);
+
+    if let Some(cwd) = cwd_snapshot {
+        let _ = env::set_current_dir(cwd);
+    }
+
+    result
+}
+
 /// Invokes the provided script content and returns a process handle.
 fn spawn_script(
     script: &str,
     args: &Vec<String>,
     options: &ScriptOptions,
 ) -> Result<(Child, String), ScriptError> {
-    match modify_script(&script.to_string(), &options) {
-        Ok(updated_script) => match create_script_file(&updated_script) {
+    let shell = options
+        .shell
+        .clone()
+        .unwrap_or_else(Shell::default_for_platform);
+
+    // `modify_script` falls back to the ambient `current_dir()` when `working_directory` is
+    // not set, and the spawned child inherits the ambient environment; hold the same lock a
+    // concurrent `Runtime::Embedded` run uses so neither can observe the other's process-global
+    // state mid-mutation.
+    let _guard = ENV_CWD_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    match modify_script(&script.to_string(), &options, &shell) {
+        Ok(updated_script) => match create_script_file(&updated_script, shell.extension()) {
             Ok(file) => {
-                let command = match options.runner {
-                    Some(ref value) => value,
-                    None => {
-                        if cfg!(windows) {
-                            "cmd.exe"
-                        } else {
-                            "sh"
-                        }
-                    }
-                };
-
-                let mut all_args = if cfg!(windows) {
-                    vec!["/C".to_string(), file.to_string()]
-                } else {
-                    vec![file.to_string()]
+                let (command, mut all_args) = match options.runner {
+                    Some(ref value) => (value.clone(), vec![file.to_string()]),
+                    None => (shell.executable().to_string(), shell.launch_args(&file)),
                 };
 
                 all_args.extend(args.iter().cloned());
@@ -222,12 +400,14 @@ fn spawn_script(
                         delete_file(&file);
 
                         Err(ScriptError {
+                            stage: Stage::Spawn,
                             info: ErrorInfo::IOError(error),
                         })
                     }
                 }
             }
             Err(error) => Err(ScriptError {
+                stage: Stage::ScriptFileCreation,
                 info: ErrorInfo::IOError(error),
             }),
         },
@@ -235,6 +415,181 @@ fn spawn_script(
     }
 }
 
+/// A handle to a `spawn_reader` thread's output.
+///
+/// `buffer` is appended to incrementally as bytes arrive, so it can be inspected at any point
+/// - including before the thread has finished - without losing data a stuck thread has already
+/// read. `done` fires once the thread reaches EOF on its pipe.
+struct ReaderHandle {
+    buffer: Arc<Mutex<Vec<u8>>>,
+    done: Receiver<()>,
+}
+
+/// Reads the given pipe to completion on a background thread, appending what it reads to a
+/// shared buffer as it arrives. Draining stdout/stderr concurrently on their own threads
+/// (rather than reading them sequentially after the child exits) avoids deadlocking when the
+/// child fills its pipe buffer before it finishes.
+///
+/// When a `line_callback` is provided, the pipe is read line by line and the callback is
+/// invoked with each line as it arrives, in addition to the full output still being
+/// accumulated for the final return value.
+fn spawn_reader<R: Read + Send + 'static>(
+    reader: R,
+    line_callback: Option<LineCallback>,
+) -> ReaderHandle {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let thread_buffer = buffer.clone();
+    let (done_sender, done_receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let append = |chunk: &[u8]| {
+            if let Ok(mut buffer) = thread_buffer.lock() {
+                buffer.extend_from_slice(chunk);
+            }
+        };
+
+        match line_callback {
+            Some(callback) => {
+                let mut buffered = BufReader::new(reader);
+                let mut line = Vec::new();
+
+                loop {
+                    line.clear();
+
+                    // read raw bytes so that a line which is not valid UTF-8 does not end the
+                    // loop and drop the rest of the output
+                    match buffered.read_until(b'\n', &mut line) {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) => {
+                            if let Ok(mut callback) = callback.lock() {
+                                let text = String::from_utf8_lossy(&line);
+                                // lines from a CRLF source (e.g. Shell::Cmd/Shell::PowerShell
+                                // on Windows) would otherwise leave a trailing '\r'
+                                callback(text.trim_end_matches(|c| c == '\r' || c == '\n'));
+                            }
+
+                            append(&line);
+                        }
+                    }
+                }
+            }
+            None => {
+                let mut reader = reader;
+                let mut chunk = [0u8; 4096];
+
+                loop {
+                    match reader.read(&mut chunk) {
+                        Ok(0) | Err(_) => break,
+                        Ok(size) => append(&chunk[..size]),
+                    }
+                }
+            }
+        }
+
+        // the receiving end is only ever dropped after giving up on a grace period, so a
+        // failed send just means nobody is listening anymore
+        let _ = done_sender.send(());
+    });
+
+    ReaderHandle {
+        buffer,
+        done: done_receiver,
+    }
+}
+
+/// Collects the bytes produced by a `spawn_reader` thread.
+///
+/// Without a deadline this blocks until the reader thread finishes (i.e. its pipe reaches
+/// EOF). With a deadline, the wait is bounded instead: this is used after a timeout-triggered
+/// kill, where an orphaned grandchild process may still be holding the pipe's write end open,
+/// which would otherwise block the reader thread (and this call) forever. Either way, whatever
+/// the thread had already appended to the shared buffer is returned - including a partial
+/// capture when the deadline is hit before the thread finishes - rather than being discarded.
+fn collect_reader_output(reader: Option<ReaderHandle>, deadline: Option<Duration>) -> Vec<u8> {
+    match reader {
+        Some(handle) => {
+            match deadline {
+                Some(duration) => {
+                    let _ = handle.done.recv_timeout(duration);
+                }
+                None => {
+                    let _ = handle.done.recv();
+                }
+            }
+
+            handle
+                .buffer
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// The result of waiting on a child process, bounded by an optional timeout.
+enum WaitOutcome {
+    /// The process exited on its own
+    Exited(ExitStatus),
+    /// The process did not exit before the deadline
+    TimedOut {
+        /// true if the process was successfully killed
+        terminated: bool,
+    },
+}
+
+/// Kills the child process along with any descendants it spawned.
+///
+/// The child is placed in its own process group at spawn time (see `create_command_builder`),
+/// so sending `SIGKILL` to the negated pid targets the whole group instead of just the shell
+/// itself. Falls back to `Child::kill` if that fails, e.g. if the group has already exited.
+#[cfg(not(windows))]
+fn kill_child_tree(child: &mut Child) -> bool {
+    let pgid = child.id() as i32;
+
+    if kill(Pid::from_raw(-pgid), Signal::SIGKILL).is_ok() {
+        true
+    } else {
+        child.kill().is_ok()
+    }
+}
+
+/// Kills the child process. Windows has no direct equivalent of a POSIX process group kill, so
+/// only the immediate child is terminated; any descendants it spawned are left running.
+#[cfg(windows)]
+fn kill_child_tree(child: &mut Child) -> bool {
+    child.kill().is_ok()
+}
+
+/// Waits for the child process to exit, bounded by the optional timeout.
+///
+/// With no timeout this simply blocks on `Child::wait`. With a timeout, the child is polled
+/// via `Child::try_wait` on a short interval and killed once the deadline passes.
+fn wait_for_child(child: &mut Child, timeout: Option<Duration>) -> Result<WaitOutcome, Error> {
+    match timeout {
+        None => child.wait().map(WaitOutcome::Exited),
+        Some(duration) => {
+            let deadline = Instant::now() + duration;
+            let poll_interval = Duration::from_millis(50);
+
+            loop {
+                if let Some(status) = child.try_wait()? {
+                    return Ok(WaitOutcome::Exited(status));
+                }
+
+                if Instant::now() >= deadline {
+                    let terminated = kill_child_tree(child);
+                    let _ = child.wait();
+
+                    return Ok(WaitOutcome::TimedOut { terminated });
+                }
+
+                thread::sleep(poll_interval);
+            }
+        }
+    }
+}
+
 /// Invokes the provided script content and returns a process handle.
 ///
 /// # Arguments
@@ -247,6 +602,16 @@ pub(crate) fn spawn(
     args: &Vec<String>,
     options: &ScriptOptions,
 ) -> Result<Child, ScriptError> {
+    if options.runtime == Runtime::Embedded {
+        return Err(ScriptError {
+            stage: Stage::Spawn,
+            info: ErrorInfo::Description(
+                "Runtime::Embedded executes in-process and has no child process handle to \
+                 return; use `run` instead.",
+            ),
+        });
+    }
+
     let result = spawn_script(script, &args, &options);
 
     match result {
@@ -267,23 +632,67 @@ pub(crate) fn run(
     args: &Vec<String>,
     options: &ScriptOptions,
 ) -> Result<(i32, String, String), ScriptError> {
+    if options.runtime == Runtime::Embedded {
+        return run_embedded(script, &args, &options);
+    }
+
     let result = spawn_script(script, &args, &options);
 
     match result {
-        Ok((child, file)) => {
-            let process_result = child.wait_with_output();
+        Ok((mut child, file)) => {
+            let stdout_reader = child
+                .stdout
+                .take()
+                .map(|pipe| spawn_reader(pipe, options.on_stdout_line.clone()));
+            let stderr_reader = child
+                .stderr
+                .take()
+                .map(|pipe| spawn_reader(pipe, options.on_stderr_line.clone()));
+
+            let wait_result = wait_for_child(&mut child, options.timeout);
 
             delete_file(&file);
 
-            match process_result {
-                Ok(output) => {
-                    let exit_code = get_exit_code(output.status);
-                    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
-                    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            // After a timeout-triggered kill, an orphaned grandchild process may still hold a
+            // pipe's write end open; give the reader threads a short grace period to flush
+            // what they can instead of blocking on them forever.
+            let read_deadline = match wait_result {
+                Ok(WaitOutcome::TimedOut { .. }) => Some(Duration::from_millis(200)),
+                _ => None,
+            };
+
+            let stdout = collect_reader_output(stdout_reader, read_deadline);
+            let stderr = collect_reader_output(stderr_reader, read_deadline);
+
+            match wait_result {
+                Ok(WaitOutcome::Exited(status)) => {
+                    let exit_code = get_exit_code(status);
+                    let stdout = String::from_utf8_lossy(&stdout).into_owned();
+                    let stderr = String::from_utf8_lossy(&stderr).into_owned();
 
-                    Ok((exit_code, stdout, stderr))
+                    if options.exit_on_error && exit_code != 0 {
+                        Err(ScriptError {
+                            stage: Stage::NonZeroExit,
+                            info: ErrorInfo::ExitCode {
+                                code: exit_code,
+                                stdout,
+                                stderr,
+                            },
+                        })
+                    } else {
+                        Ok((exit_code, stdout, stderr))
+                    }
                 }
+                Ok(WaitOutcome::TimedOut { terminated }) => Err(ScriptError {
+                    stage: Stage::Wait,
+                    info: ErrorInfo::Timeout {
+                        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+                        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+                        terminated,
+                    },
+                }),
                 Err(error) => Err(ScriptError {
+                    stage: Stage::Wait,
                     info: ErrorInfo::IOError(error),
                 }),
             }
@@ -0,0 +1,49 @@
+use super::*;
+use std::time::Instant;
+
+#[cfg(not(windows))]
+#[test]
+fn timeout_kills_background_descendant_process() {
+    // the shell itself sleeps for 2 seconds and backgrounds longer-lived processes that inherit
+    // its stdout pipe; one of them creates a marker file after 1 second, so the marker only
+    // stays absent if the timeout killed the whole process group and not just the shell
+    let mut marker = std::env::temp_dir();
+    marker.push("run_script_timeout_descendant_marker");
+    let _ = std::fs::remove_file(&marker);
+
+    let script = format!(
+        r#"
+sleep 5 &
+(sleep 1; touch "{}") &
+sleep 2
+"#,
+        marker.to_str().unwrap()
+    );
+
+    let mut options = ScriptOptions::new();
+    options.timeout = Some(Duration::from_millis(200));
+
+    let started = Instant::now();
+    let result = run(&script, &vec![], &options);
+    let elapsed = started.elapsed();
+
+    assert!(
+        elapsed < Duration::from_secs(2),
+        "run() took {:?}, expected it to return shortly after the configured timeout",
+        elapsed
+    );
+
+    match result {
+        Err(ScriptError {
+            stage: Stage::Wait,
+            info: ErrorInfo::Timeout { .. },
+        }) => (),
+        other => panic!("expected a Timeout error, got {:?}", other),
+    }
+
+    thread::sleep(Duration::from_millis(1500));
+    assert!(
+        !marker.exists(),
+        "a background descendant process was still running after the timeout"
+    );
+}
@@ -0,0 +1,342 @@
+//! # types
+//!
+//! Defines the types/structs used by this library.
+//!
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A callback invoked once per line of output as it streams in. Wrapped in `Arc<Mutex<_>>` so
+/// it can be cloned onto the reader thread while still being `FnMut`.
+pub type LineCallback = Arc<Mutex<dyn FnMut(&str) + Send>>;
+
+/// IO Type used for stdin/stdout/stderr definitions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IoOptions {
+    /// Inherit current env settings
+    Inherit,
+    /// Pipe the IO
+    Pipe,
+    /// Ignore the IO
+    Null,
+}
+
+/// Identifies which stage of script execution produced an error
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    /// Preparing the script content and/or writing it to a temp file
+    ScriptFileCreation,
+    /// Spawning/starting the shell, runner or embedded interpreter
+    Spawn,
+    /// Waiting for the script to finish running
+    Wait,
+    /// The script finished running with a non-zero exit code
+    NonZeroExit,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            Stage::ScriptFileCreation => "script file creation",
+            Stage::Spawn => "spawn",
+            Stage::Wait => "wait",
+            Stage::NonZeroExit => "non-zero exit",
+        };
+
+        write!(f, "{}", description)
+    }
+}
+
+/// Holds error information
+#[derive(Debug)]
+pub enum ErrorInfo {
+    /// IO Error
+    IOError(Error),
+    /// Description only error
+    Description(&'static str),
+    /// The script did not finish within the configured `timeout`
+    Timeout {
+        /// stdout captured before the timeout elapsed
+        stdout: String,
+        /// stderr captured before the timeout elapsed
+        stderr: String,
+        /// true if the child process was successfully killed as a result of the timeout
+        terminated: bool,
+    },
+    /// The script finished with a non-zero exit code (only returned when `exit_on_error` is
+    /// enabled)
+    ExitCode {
+        /// the exit code the script finished with
+        code: i32,
+        /// stdout captured up to the point of failure
+        stdout: String,
+        /// stderr captured up to the point of failure
+        stderr: String,
+    },
+}
+
+/// Script error information
+#[derive(Debug)]
+pub struct ScriptError {
+    /// The stage of execution that produced this error
+    pub stage: Stage,
+    /// Error information for this stage
+    pub info: ErrorInfo,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.stage)?;
+
+        match self.info {
+            ErrorInfo::IOError(ref error) => write!(f, "IO error: {}", error),
+            ErrorInfo::Description(description) => write!(f, "{}", description),
+            ErrorInfo::Timeout { terminated, .. } => write!(
+                f,
+                "script execution timed out (process terminated: {})",
+                terminated
+            ),
+            ErrorInfo::ExitCode { code, .. } => write!(f, "script exited with code {}", code),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.info {
+            ErrorInfo::IOError(ref error) => Some(error),
+            ErrorInfo::Description(_) => None,
+            ErrorInfo::Timeout { .. } => None,
+            ErrorInfo::ExitCode { .. } => None,
+        }
+    }
+}
+
+/// The interpreter used to invoke the script.
+///
+/// Selecting a variant here (instead of leaving it unset) lets the caller target a specific
+/// shell explicitly rather than relying on the platform default, while still honoring each
+/// shell's own conventions for invocation, temp-file extension and strict-mode/trace support.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    /// The POSIX `sh` shell
+    Sh,
+    /// The `bash` shell
+    Bash,
+    /// The `zsh` shell
+    Zsh,
+    /// The `fish` shell
+    Fish,
+    /// Windows PowerShell
+    PowerShell,
+    /// The Windows `cmd.exe` shell
+    Cmd,
+}
+
+impl Shell {
+    /// Returns the default shell for the current platform
+    pub fn default_for_platform() -> Shell {
+        if cfg!(windows) {
+            Shell::Cmd
+        } else {
+            Shell::Sh
+        }
+    }
+
+    /// Returns the executable name used to invoke this shell
+    pub(crate) fn executable(&self) -> &'static str {
+        match self {
+            Shell::Sh => "sh",
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::PowerShell => "powershell",
+            Shell::Cmd => "cmd.exe",
+        }
+    }
+
+    /// Returns the command line arguments used to launch the given script file
+    pub(crate) fn launch_args(&self, file: &str) -> Vec<String> {
+        match self {
+            Shell::PowerShell => vec![
+                "-NoProfile".to_string(),
+                "-File".to_string(),
+                file.to_string(),
+            ],
+            Shell::Cmd => vec!["/C".to_string(), file.to_string()],
+            _ => vec![file.to_string()],
+        }
+    }
+
+    /// Returns the temp script file extension used by this shell
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            Shell::PowerShell => "ps1",
+            Shell::Cmd => "bat",
+            _ => "sh",
+        }
+    }
+
+    /// True if this shell honors a `#!` shebang as its first line
+    pub(crate) fn supports_shebang(&self) -> bool {
+        match self {
+            Shell::PowerShell | Shell::Cmd => false,
+            _ => true,
+        }
+    }
+
+    /// Returns the command used to change the working directory for this shell
+    ///
+    /// The directory is quoted, since it is a user-supplied path that may contain spaces (e.g.
+    /// `C:\Program Files\app` or `/Users/jane doe/project`); without quoting, such a path would
+    /// be split into multiple tokens and fail with a confusing shell syntax error.
+    pub(crate) fn change_directory_command(&self, directory: &str) -> String {
+        match self {
+            Shell::PowerShell => format!("Set-Location \"{}\"", directory),
+            // plain `cd` cannot switch drives on cmd.exe; `/d` makes it behave like the other
+            // shells when the working directory is on a different drive than the script file
+            Shell::Cmd => format!("cd /d \"{}\"", directory),
+            _ => format!("cd \"{}\"", directory),
+        }
+    }
+
+    /// Returns the strict-mode/trace prologue lines for this shell, based on the provided
+    /// options.
+    ///
+    /// Fish and cmd.exe have no equivalent of POSIX `set -e`/`set -x`, so both options are
+    /// treated as a no-op on those shells rather than emitting invalid syntax.
+    pub(crate) fn prologue_lines(&self, options: &ScriptOptions) -> Vec<String> {
+        let mut lines = vec![];
+
+        match self {
+            Shell::Sh | Shell::Bash | Shell::Zsh => {
+                if options.exit_on_error {
+                    lines.push("set -e".to_string());
+                }
+                if options.print_commands {
+                    lines.push("set -x".to_string());
+                }
+            }
+            Shell::PowerShell => {
+                if options.exit_on_error {
+                    lines.push("$ErrorActionPreference = 'Stop'".to_string());
+                }
+                if options.print_commands {
+                    lines.push("Set-PSDebug -Trace 1".to_string());
+                }
+            }
+            Shell::Fish | Shell::Cmd => {}
+        }
+
+        lines
+    }
+}
+
+/// Selects the backend used to execute the script.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Runtime {
+    /// Write the script to a temp file and invoke it with an external shell process, as
+    /// configured via `runner`/`shell`
+    ExternalShell,
+    /// Execute the script with the embedded duckscript interpreter instead of spawning an
+    /// external shell process, so the same script text behaves identically on every platform
+    Embedded,
+}
+
+/// Holds options available for invoking the script
+#[derive(Clone)]
+pub struct ScriptOptions {
+    /// The backend used to execute the script. Defaults to `Runtime::ExternalShell`.
+    pub runtime: Runtime,
+    /// If provided, overrides the shell executable used to invoke the script, taking precedence
+    /// over `shell`
+    pub runner: Option<String>,
+    /// The shell to invoke the script with. Defaults to the platform shell (`cmd.exe` on
+    /// windows, `sh` elsewhere) when not provided.
+    pub shell: Option<Shell>,
+    /// Print the executed commands to the console output. Has no effect with `Shell::Fish`,
+    /// `Shell::Cmd` or `Runtime::Embedded`.
+    pub print_commands: bool,
+    /// Stop on any error, similar to 'set -e' option in unix based bash shells. When enabled,
+    /// a non-zero final exit code is also surfaced as an `ErrorInfo::ExitCode` error instead of
+    /// being returned as part of the `Ok` tuple. With `Shell::Fish` and `Shell::Cmd` only that
+    /// final exit code check applies; no stop-on-error prologue is added to the script.
+    pub exit_on_error: bool,
+    /// Defines the IO of the child process input. `Runtime::Embedded` has no child process
+    /// stdin to redirect, so it only accepts `IoOptions::Inherit` here.
+    pub capture_input: IoOptions,
+    /// Defines the IO of the child process output (stdout and stderr). `Runtime::Embedded`
+    /// runs in-process and cannot redirect its own stdout/stderr, so it only accepts
+    /// `IoOptions::Inherit` here.
+    pub capture_output: IoOptions,
+    /// If provided, the script is killed and a `Timeout` error is returned if it does not
+    /// finish within this duration. Not applied with `Runtime::Embedded`, which always runs the
+    /// script to completion.
+    pub timeout: Option<Duration>,
+    /// Environment variables to set on the child process, applied via `Command::envs`. With
+    /// `Runtime::Embedded` they are set on the calling process for the duration of the run.
+    pub env: HashMap<String, String>,
+    /// If true, the child process does not inherit the calling process's environment;
+    /// only the variables in `env` are set
+    pub clear_env: bool,
+    /// The working directory the script runs in. Defaults to the calling process's current
+    /// working directory when not provided.
+    pub working_directory: Option<PathBuf>,
+    /// Optional callback invoked with each line of stdout as it streams in. Only takes effect
+    /// when `capture_output` is `IoOptions::Pipe`.
+    pub on_stdout_line: Option<LineCallback>,
+    /// Optional callback invoked with each line of stderr as it streams in. Only takes effect
+    /// when `capture_output` is `IoOptions::Pipe`.
+    pub on_stderr_line: Option<LineCallback>,
+}
+
+impl fmt::Debug for ScriptOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScriptOptions")
+            .field("runtime", &self.runtime)
+            .field("runner", &self.runner)
+            .field("shell", &self.shell)
+            .field("print_commands", &self.print_commands)
+            .field("exit_on_error", &self.exit_on_error)
+            .field("capture_input", &self.capture_input)
+            .field("capture_output", &self.capture_output)
+            .field("timeout", &self.timeout)
+            .field("env", &self.env)
+            .field("clear_env", &self.clear_env)
+            .field("working_directory", &self.working_directory)
+            .field("on_stdout_line", &self.on_stdout_line.is_some())
+            .field("on_stderr_line", &self.on_stderr_line.is_some())
+            .finish()
+    }
+}
+
+impl ScriptOptions {
+    /// Returns new instance with default values
+    pub fn new() -> ScriptOptions {
+        ScriptOptions {
+            runtime: Runtime::ExternalShell,
+            runner: None,
+            shell: None,
+            print_commands: false,
+            exit_on_error: false,
+            capture_input: IoOptions::Inherit,
+            capture_output: IoOptions::Pipe,
+            timeout: None,
+            env: HashMap::new(),
+            clear_env: false,
+            working_directory: None,
+            on_stdout_line: None,
+            on_stderr_line: None,
+        }
+    }
+}
+
+impl Default for ScriptOptions {
+    fn default() -> Self {
+        ScriptOptions::new()
+    }
+}